@@ -0,0 +1,252 @@
+//! Write-through in-memory cache over a [`FileStore`], so hot sessions don't hit the disk on
+//! every request.
+
+use axum::async_trait;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tower_sessions::{
+    session::{Id, Record},
+    session_store::{self, SessionStore},
+    ExpiredDeletion,
+};
+
+use crate::FileStore;
+
+/// A cached `Record` plus the `Instant` it was cached at, used to honor the cache's own TTL
+/// independently of the session's `expiry_date`.
+#[derive(Clone, Debug)]
+struct CachedRecord {
+    record: Record,
+    cached_at: Instant,
+}
+
+/// Wraps a [`FileStore`] with a bounded, write-through in-memory cache keyed by `Id`.  `load`
+/// checks the cache first and only falls back to disk on a miss; `create`/`save` write through to
+/// both the cache and disk; `delete` evicts from both.  Session expiry is honored on cache hits
+/// just as it is on disk reads, and entries older than `ttl` are treated as misses and refreshed
+/// from disk.
+#[derive(Clone, Debug)]
+pub struct CachedFileStore {
+    inner: FileStore,
+    cache: Arc<DashMap<Id, CachedRecord>>,
+    /// Maximum number of entries to retain in the cache.  Once full, an arbitrary entry is
+    /// evicted to make room for a new one; `0` disables caching entirely.
+    capacity: usize,
+    /// Maximum age of a cache entry before it's treated as a miss and re-read from disk.
+    ttl: Duration,
+}
+
+impl CachedFileStore {
+    /// Wraps `inner` with an in-memory cache bounded to `capacity` entries, each valid for at most
+    /// `ttl` before it's refreshed from disk.
+    pub fn new(inner: FileStore, capacity: usize, ttl: Duration) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(DashMap::new()),
+            capacity,
+            ttl,
+        }
+    }
+    /// Returns the cached `Record` for `session_id`, evicting it first if it's expired or has
+    /// outlived the cache's `ttl`.
+    fn cache_get(&self, session_id: &Id) -> Option<Record> {
+        let entry = self.cache.get(session_id)?;
+        let stale = entry.cached_at.elapsed() > self.ttl;
+        let expired = entry.record.expiry_date < OffsetDateTime::now_utc();
+        if stale || expired {
+            drop(entry);
+            self.cache.remove(session_id);
+            return None;
+        }
+        Some(entry.record.clone())
+    }
+    /// Writes `record` into the cache, evicting an arbitrary entry first if `capacity` has been
+    /// reached.
+    fn cache_put(&self, record: Record) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.cache.len() >= self.capacity && !self.cache.contains_key(&record.id) {
+            if let Some(evict_id) = self.cache.iter().next().map(|e| *e.key()) {
+                self.cache.remove(&evict_id);
+            }
+        }
+        self.cache.insert(
+            record.id,
+            CachedRecord {
+                record,
+                cached_at: Instant::now(),
+            },
+        );
+    }
+}
+
+#[async_trait]
+impl SessionStore for CachedFileStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        self.inner.create(record).await?;
+        self.cache_put(record.clone());
+        Ok(())
+    }
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.inner.save(record).await?;
+        self.cache_put(record.clone());
+        Ok(())
+    }
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        if let Some(record) = self.cache_get(session_id) {
+            return Ok(Some(record));
+        }
+        let loaded = self.inner.load(session_id).await?;
+        if let Some(record) = &loaded {
+            self.cache_put(record.clone());
+        }
+        Ok(loaded)
+    }
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.inner.delete(session_id).await?;
+        self.cache.remove(session_id);
+        Ok(())
+    }
+}
+
+#[async_trait]
+/// Expiry sweeps operate on the backing `FileStore`; cache entries for deleted sessions are
+/// reaped lazily via `cache_get`'s own expiry check rather than scanned eagerly here.
+impl ExpiredDeletion for CachedFileStore {
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        self.inner.delete_expired().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Builds a `FileStore` rooted in a fresh, uniquely-named directory under the OS temp dir.
+    /// Leaks the path string since `FileStore::dir` is `&'static str` -- acceptable for
+    /// short-lived test processes.
+    fn test_inner_store(name: &str) -> FileStore {
+        let dir = std::env::temp_dir().join(format!(
+            "tower-sessions-file-store-cached-test-{name}-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir: &'static str = Box::leak(dir.to_string_lossy().into_owned().into_boxed_str());
+        FileStore::new(dir, "", ".json")
+    }
+
+    fn sample_record(id: i128) -> Record {
+        Record {
+            id: Id(id),
+            data: HashMap::new(),
+            expiry_date: OffsetDateTime::now_utc() + time::Duration::minutes(5),
+        }
+    }
+
+    fn sample_expired_record(id: i128) -> Record {
+        Record {
+            id: Id(id),
+            data: HashMap::new(),
+            expiry_date: OffsetDateTime::now_utc() - time::Duration::minutes(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn load_serves_from_cache_without_touching_disk() {
+        let inner = test_inner_store("cache-hit");
+        let record = sample_record(1);
+        let store = CachedFileStore::new(inner.clone(), 10, Duration::from_secs(60));
+        store.save(&record).await.unwrap();
+
+        // Pull the file out from under the cache; a disk read would now find nothing.
+        std::fs::remove_file(inner.path(&record.id)).unwrap();
+
+        let loaded = store.load(&record.id).await.unwrap();
+        assert_eq!(loaded.unwrap().id, record.id);
+    }
+
+    #[tokio::test]
+    async fn save_and_create_write_through_to_disk() {
+        let inner = test_inner_store("write-through");
+        let store = CachedFileStore::new(inner.clone(), 10, Duration::from_secs(60));
+        let mut record = sample_record(2);
+
+        store.create(&mut record).await.unwrap();
+        assert!(inner.load(&record.id).await.unwrap().is_some());
+
+        let record2 = sample_record(3);
+        store.save(&record2).await.unwrap();
+        assert!(inner.load(&record2.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn delete_evicts_cache_entry() {
+        let inner = test_inner_store("delete-evicts");
+        let store = CachedFileStore::new(inner.clone(), 10, Duration::from_secs(60));
+        let record = sample_record(4);
+        store.save(&record).await.unwrap();
+        store.load(&record.id).await.unwrap(); // warm the cache
+
+        store.delete(&record.id).await.unwrap();
+
+        // Write a fresh record under the same id directly to disk; if the stale cache entry were
+        // still present, `load` would return the old data instead of this one.
+        let replacement = Record {
+            data: {
+                let mut data = HashMap::new();
+                data.insert("marker".to_string(), serde_json::Value::Bool(true));
+                data
+            },
+            ..sample_record(4)
+        };
+        inner.save(&replacement).await.unwrap();
+
+        let loaded = store.load(&record.id).await.unwrap().unwrap();
+        assert_eq!(loaded.data, replacement.data);
+    }
+
+    #[tokio::test]
+    async fn capacity_bounded_cache_evicts_an_entry_to_make_room() {
+        let inner = test_inner_store("capacity");
+        let store = CachedFileStore::new(inner.clone(), 1, Duration::from_secs(60));
+        let a = sample_record(5);
+        let b = sample_record(6);
+        store.save(&a).await.unwrap();
+        store.save(&b).await.unwrap(); // capacity 1: should evict `a` from the cache
+
+        // Remove `a`'s file directly on disk; if `a` were still cached, `load` would still find it.
+        std::fs::remove_file(inner.path(&a.id)).unwrap();
+
+        let loaded = store.load(&a.id).await.unwrap();
+        assert!(loaded.is_none());
+    }
+
+    #[tokio::test]
+    async fn ttl_expiry_treats_stale_cache_entries_as_misses() {
+        let inner = test_inner_store("ttl");
+        let store = CachedFileStore::new(inner.clone(), 10, Duration::from_millis(1));
+        let record = sample_record(7);
+        store.save(&record).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        std::fs::remove_file(inner.path(&record.id)).unwrap();
+
+        let loaded = store.load(&record.id).await.unwrap();
+        assert!(loaded.is_none());
+    }
+
+    #[tokio::test]
+    async fn load_evicts_cache_entry_past_its_own_expiry() {
+        let inner = test_inner_store("expiry-on-hit");
+        let store = CachedFileStore::new(inner.clone(), 10, Duration::from_secs(60));
+        let record = sample_expired_record(8);
+        store.save(&record).await.unwrap();
+
+        let loaded = store.load(&record.id).await.unwrap();
+        assert!(loaded.is_none());
+    }
+}
@@ -1,8 +1,8 @@
 //! # tower-sessions-file-store
-//! 
+//!
 //! `tower-sessions-file-store` is a simple and minimalistic file store backing provider for
 //! `tower-sessions`.  Usage is extremely simple;
-//! 
+//!
 //! ## Example:
 //! ```
 //!     let session_store = tower_sessions_file_store::FileStore::new("/path/to/sessions/directory", "prefix-", ".json");
@@ -14,18 +14,27 @@
 //!         .route("/sess_test", get(handle_sess_test));
 //!         .layer(session_layer)
 //!         ;
-//!     
+//!
 //!     /* ... Elsewhere ... */
 //!     async fn handle_sess_test(sess: tower_sessions::Session) -> impl axum::response::IntoResponse {
 //!         let counter: u32 = sess.get("count").await.unwrap().unwrap_or(0u32);
 //!         let _ = sess.insert("count", counter + 1).await;
 //!         format!("Count is {counter}.")
 //!     }
-//!     
+//!
 //! ```
 
+mod cached;
+pub use cached::CachedFileStore;
+
 use axum::async_trait;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use fs2::FileExt;
 use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
 use tower_sessions::{
     self,
     session::{Id, Record},
@@ -33,31 +42,112 @@ use tower_sessions::{
         self,
         Error::Decode,
     },
+    ExpiredDeletion,
 };
 
+/// Monotonic counter used to keep temporary file names unique across concurrent saves within the
+/// same process.
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Serialization format used to persist `Record`s to disk.  `Json` is the default and keeps
+/// session files human-readable; `Bincode` and `MessagePack` are more compact binary alternatives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SerializationFormat {
+    #[default]
+    Json,
+    Bincode,
+    MessagePack,
+}
+
+impl SerializationFormat {
+    /// Returns the file extension this format is conventionally stored under, used as a fallback
+    /// when `FileStore::extension` is left blank.
+    fn default_extension(self) -> &'static str {
+        match self {
+            SerializationFormat::Json => ".json",
+            SerializationFormat::Bincode => ".bin",
+            SerializationFormat::MessagePack => ".msgpack",
+        }
+    }
+    /// Serializes `record` according to this format.
+    fn serialize(self, record: &Record) -> session_store::Result<Vec<u8>> {
+        match self {
+            SerializationFormat::Json => {
+                serde_json::to_vec(record).map_err(|e| Decode(e.to_string()))
+            }
+            SerializationFormat::Bincode => {
+                bincode::serialize(record).map_err(|e| Decode(e.to_string()))
+            }
+            SerializationFormat::MessagePack => {
+                rmp_serde::to_vec(record).map_err(|e| Decode(e.to_string()))
+            }
+        }
+    }
+    /// Deserializes a `Record` previously written with `serialize` in this same format.
+    fn deserialize(self, data: &[u8]) -> session_store::Result<Record> {
+        match self {
+            SerializationFormat::Json => {
+                serde_json::from_slice(data).map_err(|e| Decode(e.to_string()))
+            }
+            SerializationFormat::Bincode => {
+                bincode::deserialize(data).map_err(|e| Decode(e.to_string()))
+            }
+            SerializationFormat::MessagePack => {
+                rmp_serde::from_slice(data).map_err(|e| Decode(e.to_string()))
+            }
+        }
+    }
+}
+
 /// Creates a FileStore struct and stores its configuration.  Specifying the `dir`, `prefix`, and
 /// `extension` fields will define how session.
-/// 
+///
 /// For example, if you were to use:
 /// ```
 ///     FileStore::new("/path/to/sesssions/directory", "prefix-", ".json")
 /// ```
 /// to instantiate a new `FileStore` struct, then you would end up with files such as:
-/// 
+///
 /// ```bash
 ///     /path/to/sesssions/directory/prefix-CI4afkzk6tVMRb50lMyZAA.json
 ///     /path/to/sesssions/directory/prefix-Hs8Jb0_zAGrc_rmUYGwlvw.json
 ///     /path/to/sesssions/directory/prefix-swJdTjvk1os8zAhhc6AVMQ.json
 /// ```
-/// 
-/// 
-#[derive(Clone, Debug, Default)]
+///
+///
+#[derive(Clone, Default)]
 pub struct FileStore {
     /// Directory to use for session storage.  Omit any trailing slashes or path separators.
     pub dir: &'static str,
     /// Optional prefix for session files.  If not empty, all files will begin with this prefix
     pub prefix: &'static str,
     pub extension: &'static str,
+    /// When `true`, `save` takes an exclusive advisory lock on a per-session `.lock` file around
+    /// the write-rename sequence, serializing concurrent saves to the same `Id`.  Disabled by
+    /// default since it costs an extra file open/lock per save.
+    pub locking: bool,
+    /// Format used to serialize and deserialize session records.  Defaults to `Json`.
+    pub format: SerializationFormat,
+    /// When set, session files are encrypted at rest with ChaCha20-Poly1305 under this key.  See
+    /// `with_encryption`.
+    encryption_key: Option<[u8; 32]>,
+}
+
+impl std::fmt::Debug for FileStore {
+    /// Redacts `encryption_key` rather than printing key material.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileStore")
+            .field("dir", &self.dir)
+            .field("prefix", &self.prefix)
+            .field("extension", &self.extension)
+            .field("locking", &self.locking)
+            .field("format", &self.format)
+            .field(
+                "encryption_key",
+                &self.encryption_key.map(|_| "<redacted>"),
+            )
+            .finish()
+    }
 }
 
 impl FileStore {
@@ -67,6 +157,7 @@ impl FileStore {
             dir,
             prefix,
             extension,
+            ..Default::default()
         }
     }
     /// Creates a new `FileStore` struct with the specified `dir` field and blank `prefix` and
@@ -76,6 +167,71 @@ impl FileStore {
             dir: d,
             prefix: "",
             extension: "",
+            ..Default::default()
+        }
+    }
+    /// Enables (or disables) advisory file locking around `save`.  When enabled, concurrent saves
+    /// for the same session `Id` are serialized against one another via an exclusive lock on a
+    /// `.lock` file living alongside the session file, rather than racing to rename over each
+    /// other.  `delete` and `delete_expired` remove the `.lock` file along with the session file
+    /// so it doesn't linger after the session it guards is gone.
+    pub fn with_locking(mut self, locking: bool) -> Self {
+        self.locking = locking;
+        self
+    }
+    /// Selects the `SerializationFormat` used to persist session records.
+    pub fn with_format(mut self, format: SerializationFormat) -> Self {
+        self.format = format;
+        self
+    }
+    /// Enables authenticated encryption of session files at rest under `key` using
+    /// ChaCha20-Poly1305.  `save` encrypts the serialized record with a fresh random nonce and
+    /// writes `nonce || ciphertext` (the tag is appended to the ciphertext by the AEAD crate) to
+    /// disk; `load` reverses this and returns a `Decode` error if the authentication tag fails to
+    /// verify, which catches tampering as well as a key mismatch.
+    pub fn with_encryption(mut self, key: [u8; 32]) -> Self {
+        self.encryption_key = Some(key);
+        self
+    }
+    /// Encrypts `plaintext` under `encryption_key` if set, prefixing the ciphertext with its
+    /// nonce; otherwise returns `plaintext` unchanged.
+    fn encrypt(&self, plaintext: &[u8]) -> session_store::Result<Vec<u8>> {
+        let Some(key_bytes) = self.encryption_key else {
+            return Ok(plaintext.to_vec());
+        };
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key_bytes));
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| Decode(e.to_string()))?;
+        let mut out = Vec::with_capacity(nonce.len() + ciphertext.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+    /// Reverses `encrypt`.  Returns `data` unchanged if no `encryption_key` is set; otherwise
+    /// splits off the leading nonce and decrypts the remainder, failing with a `Decode` error if
+    /// the authentication tag doesn't verify.
+    fn decrypt(&self, data: &[u8]) -> session_store::Result<Vec<u8>> {
+        let Some(key_bytes) = self.encryption_key else {
+            return Ok(data.to_vec());
+        };
+        if data.len() < 12 {
+            return Err(Decode("encrypted session file is shorter than a nonce".to_string()));
+        }
+        let (nonce_bytes, ciphertext) = data.split_at(12);
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&key_bytes));
+        cipher
+            .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+            .map_err(|e| Decode(e.to_string()))
+    }
+    /// Returns the extension session files are written with: `self.extension` if set, otherwise
+    /// the conventional extension for `self.format`.
+    fn effective_extension(&self) -> &str {
+        if self.extension.is_empty() {
+            self.format.default_extension()
+        } else {
+            self.extension
         }
     }
     /// Returns the full path a session with the given `session_id` should be found or created at.
@@ -85,16 +241,91 @@ impl FileStore {
             + std::path::MAIN_SEPARATOR.to_string().as_str()
             + self.prefix
             + session_id.to_string().as_str()
-            + self.extension
+            + self.effective_extension()
+    }
+    /// Returns the path of the advisory lock file used to serialize saves for `session_id` when
+    /// `locking` is enabled.
+    fn lock_path(&self, session_id: &Id) -> String {
+        self.path(session_id) + ".lock"
     }
-    /// Internal function for saving a session.  Note that depending on the host file system, this
-    /// could be susceptible to clobbering / race conditions.  If you expect multiple concurrent
-    /// saves to the same session ID, this may not be the ideal tool for you to use.  Its chief
-    /// goals are simplicity and lack of reliance upon external tooling and if you seek stronger
-    /// ACID guarantees you should consider another storage system.
-    fn save(&self, record: &Record) -> session_store::Result<()> {
-        let serialized = serde_json::to_string(&record).map_err(|e| Decode(e.to_string()))?;
-        fs::write(self.path(&record.id), serialized).map_err(|e| Decode(e.to_string()))
+    /// Takes an exclusive advisory lock on the `.lock` file at `lock_path`, creating it if
+    /// necessary.  The lock is released when the returned `File` is dropped.  Runs blocking I/O,
+    /// so callers invoke this from within `spawn_blocking`.
+    fn lock_session_blocking(lock_path: &str) -> session_store::Result<fs::File> {
+        let lock_file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(lock_path)
+            .map_err(|e| Decode(e.to_string()))?;
+        lock_file.lock_exclusive().map_err(|e| Decode(e.to_string()))?;
+        Ok(lock_file)
+    }
+    /// Internal function for saving a session.  Serializes the record into a uniquely-named
+    /// temporary file in the same directory as the target, `flush`/`sync_all`s it, then
+    /// `fs::rename`s it over `self.path(&record.id)`.  On POSIX, rename within a single
+    /// filesystem is atomic, so a concurrent `load` always observes either the old or the new
+    /// complete file, never a truncated one.  The temporary file is removed on any error path.
+    /// If `locking` is enabled, the write-rename sequence happens while holding an exclusive
+    /// lock on the session's `.lock` file, serializing concurrent saves to the same `Id`.
+    ///
+    /// The serialize-and-write step uses `tokio::fs` so it doesn't park a worker thread; taking
+    /// the advisory lock and renaming are true blocking syscalls, so those steps run inside
+    /// `spawn_blocking`.
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let serialized = self.format.serialize(record)?;
+        let serialized = self.encrypt(&serialized)?;
+        let final_path = self.path(&record.id);
+        let counter = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp_path = format!("{final_path}.{}.{counter}.tmp", std::process::id());
+
+        let _lock_guard = match self.locking.then(|| self.lock_path(&record.id)) {
+            Some(lock_path) => Some(
+                tokio::task::spawn_blocking(move || Self::lock_session_blocking(&lock_path))
+                    .await
+                    .map_err(|e| Decode(e.to_string()))??,
+            ),
+            None => None,
+        };
+
+        let write_result: std::io::Result<()> = async {
+            let mut file = tokio::fs::File::create(&temp_path).await?;
+            file.write_all(&serialized).await?;
+            file.sync_all().await
+        }
+        .await;
+        if let Err(e) = write_result {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(Decode(e.to_string()));
+        }
+
+        let rename_temp_path = temp_path.clone();
+        let rename_final_path = final_path.clone();
+        let rename_result = tokio::task::spawn_blocking(move || -> session_store::Result<()> {
+            fs::rename(&rename_temp_path, &rename_final_path).map_err(|e| Decode(e.to_string()))
+        })
+        .await
+        .map_err(|e| Decode(e.to_string()))
+        .and_then(|r| r);
+
+        if let Err(e) = rename_result {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        Ok(())
+    }
+    /// Periodically calls `delete_expired` on a tokio interval.  Intended to be spawned as its own
+    /// long-running task alongside the session layer; runs until the process exits, the task is
+    /// aborted, or a `delete_expired` sweep returns an error, which is then returned.
+    pub async fn continuously_delete_expired(
+        self: std::sync::Arc<Self>,
+        period: std::time::Duration,
+    ) -> session_store::Result<()> {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            self.delete_expired().await?;
+        }
     }
 }
 
@@ -104,19 +335,218 @@ impl FileStore {
 /// Note that the self.save() and self.path() calls refer to `impl FileStore`
 impl session_store::SessionStore for FileStore {
     async fn create(&self, record: &mut Record) -> session_store::Result<()> {
-        self.save(record)
+        self.save(record).await
     }
     async fn save(&self, record: &Record) -> session_store::Result<()> {
-        self.save(record)
+        self.save(record).await
     }
     async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
-        let data: String = fs::read_to_string(self.path(session_id)).map_err(|e| Decode(e.to_string()))?;
-        let record: Record = serde_json::from_str(data.as_str()).map_err(|e| Decode(e.to_string()))?;
+        let path = self.path(session_id);
+        let data = match tokio::fs::read(&path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Decode(e.to_string())),
+        };
+        let data = self.decrypt(&data)?;
+        let record = self.format.deserialize(&data)?;
+        if record.expiry_date < OffsetDateTime::now_utc() {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Ok(None);
+        }
         Ok(Some(record))
     }
     async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
-        fs::remove_file(self.path(session_id)).map_err(|e| Decode(e.to_string()))
+        tokio::fs::remove_file(self.path(session_id)).await.map_err(|e| Decode(e.to_string()))?;
+        let _ = tokio::fs::remove_file(self.lock_path(session_id)).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+/// Implementation of tower_sessions::ExpiredDeletion
+/// Scans `self.dir` for session files and removes those whose `expiry_date` has passed.  Can be
+/// called on demand, or run on a recurring schedule via `continuously_delete_expired`.
+impl ExpiredDeletion for FileStore {
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        let now = OffsetDateTime::now_utc();
+        let mut entries = tokio::fs::read_dir(self.dir).await.map_err(|e| Decode(e.to_string()))?;
+        while let Some(entry) = entries.next_entry().await.map_err(|e| Decode(e.to_string()))? {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            if !self.prefix.is_empty() && !file_name.starts_with(self.prefix) {
+                continue;
+            }
+            if !file_name.ends_with(self.effective_extension()) {
+                continue;
+            }
+            let path = entry.path();
+            let Ok(data) = tokio::fs::read(&path).await else {
+                continue;
+            };
+            let Ok(data) = self.decrypt(&data) else {
+                continue;
+            };
+            let Ok(record) = self.format.deserialize(&data) else {
+                continue;
+            };
+            if record.expiry_date < now {
+                let _ = tokio::fs::remove_file(&path).await;
+                let _ = tokio::fs::remove_file(self.lock_path(&record.id)).await;
+            }
+        }
+        Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tower_sessions::session_store::SessionStore;
+
+    /// Builds a `FileStore` rooted in a fresh, uniquely-named directory under the OS temp dir.
+    /// Leaks the path string since `FileStore::dir` is `&'static str` -- acceptable for
+    /// short-lived test processes.
+    fn test_store(name: &str) -> FileStore {
+        let dir = std::env::temp_dir().join(format!(
+            "tower-sessions-file-store-test-{name}-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let dir: &'static str = Box::leak(dir.to_string_lossy().into_owned().into_boxed_str());
+        FileStore::new(dir, "", ".json")
+    }
+
+    fn sample_record(id: i128) -> Record {
+        Record {
+            id: Id(id),
+            data: HashMap::new(),
+            expiry_date: OffsetDateTime::now_utc() + time::Duration::minutes(5),
+        }
+    }
+
+    fn sample_expired_record(id: i128) -> Record {
+        Record {
+            id: Id(id),
+            data: HashMap::new(),
+            expiry_date: OffsetDateTime::now_utc() - time::Duration::minutes(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn save_then_load_round_trips() {
+        let store = test_store("roundtrip");
+        let record = sample_record(1);
+        store.save(&record).await.unwrap();
+        let loaded = store.load(&record.id).await.unwrap();
+        assert_eq!(loaded.unwrap().id, record.id);
+    }
+
+    #[tokio::test]
+    async fn save_leaves_no_temp_file_behind() {
+        let store = test_store("no-temp-leftovers");
+        let record = sample_record(2);
+        store.save(&record).await.unwrap();
+        let leftovers = fs::read_dir(store.dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .any(|e| e.file_name().to_string_lossy().ends_with(".tmp"));
+        assert!(!leftovers);
+    }
+
+    #[tokio::test]
+    async fn concurrent_locked_saves_both_succeed_and_load_reads_a_complete_record() {
+        let store = test_store("locking").with_locking(true);
+        let record = sample_record(3);
+        let (a, b) = tokio::join!(store.save(&record), store.save(&record));
+        a.unwrap();
+        b.unwrap();
+        let loaded = store.load(&record.id).await.unwrap();
+        assert_eq!(loaded.unwrap().id, record.id);
+    }
+
+    #[tokio::test]
+    async fn delete_removes_both_session_file_and_lock_file() {
+        let store = test_store("delete-lock").with_locking(true);
+        let record = sample_record(4);
+        store.save(&record).await.unwrap();
+        assert!(std::path::Path::new(&store.lock_path(&record.id)).exists());
+
+        store.delete(&record.id).await.unwrap();
+        assert!(!std::path::Path::new(&store.path(&record.id)).exists());
+        assert!(!std::path::Path::new(&store.lock_path(&record.id)).exists());
+    }
+
+    #[tokio::test]
+    async fn expired_record_is_not_returned_by_load() {
+        let store = test_store("expired-load");
+        let record = sample_expired_record(10);
+        store.save(&record).await.unwrap();
+
+        let loaded = store.load(&record.id).await.unwrap();
+        assert!(loaded.is_none());
+        assert!(!std::path::Path::new(&store.path(&record.id)).exists());
+    }
+
+    #[tokio::test]
+    async fn delete_expired_removes_only_expired_files() {
+        let store = test_store("delete-expired");
+        let live = sample_record(11);
+        let expired = sample_expired_record(12);
+        store.save(&live).await.unwrap();
+        store.save(&expired).await.unwrap();
+
+        store.delete_expired().await.unwrap();
+
+        assert!(std::path::Path::new(&store.path(&live.id)).exists());
+        assert!(!std::path::Path::new(&store.path(&expired.id)).exists());
+    }
+
+    #[tokio::test]
+    async fn messagepack_round_trips() {
+        let store = test_store("messagepack-roundtrip").with_format(SerializationFormat::MessagePack);
+        let record = sample_record(13);
+        store.save(&record).await.unwrap();
+        let loaded = store.load(&record.id).await.unwrap();
+        assert_eq!(loaded.unwrap().id, record.id);
+    }
 
+    #[tokio::test]
+    async fn encrypted_bincode_save_then_load_round_trips() {
+        let store = test_store("encrypted-roundtrip")
+            .with_format(SerializationFormat::Bincode)
+            .with_encryption([7u8; 32]);
+        let record = sample_record(5);
+        store.save(&record).await.unwrap();
+        let loaded = store.load(&record.id).await.unwrap();
+        assert_eq!(loaded.unwrap().id, record.id);
+    }
+
+    #[tokio::test]
+    async fn load_rejects_tampered_ciphertext() {
+        let store = test_store("tamper-detect").with_encryption([9u8; 32]);
+        let record = sample_record(6);
+        store.save(&record).await.unwrap();
+
+        let path = store.path(&record.id);
+        let mut data = fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        fs::write(&path, data).unwrap();
+
+        let result = store.load(&record.id).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn load_rejects_wrong_key() {
+        let store = test_store("wrong-key").with_encryption([1u8; 32]);
+        let record = sample_record(7);
+        store.save(&record).await.unwrap();
+
+        let wrong_key_store = FileStore::new(store.dir, store.prefix, store.extension)
+            .with_encryption([2u8; 32]);
+        let result = wrong_key_store.load(&record.id).await;
+        assert!(result.is_err());
+    }
+}